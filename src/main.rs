@@ -1,15 +1,49 @@
-use blurhash::encode;
-use clap::Parser;
-use image::GenericImageView;
-use std::path::Path;
-use reqwest::blocking::get;
+use base64::Engine;
+use blurhash::{decode, encode};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use image::{DynamicImage, GenericImageView, ImageFormat, RgbaImage};
+use percent_encoding::percent_decode_str;
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::redirect::Policy;
+use serde::Serialize;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use url::Url;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// Input image file or URL
-    image: String,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Encode one or more images into blurhash strings
+    Encode(EncodeArgs),
+
+    /// Decode a blurhash back into an image or `data:` URL
+    Decode(DecodeArgs),
+}
+
+#[derive(Args, Default)]
+struct EncodeArgs {
+    /// Input image files or URLs
+    #[arg(value_name = "IMAGE")]
+    inputs: Vec<String>,
+
+    /// Glob pattern selecting input images, e.g. `*.jpg` (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    glob: Vec<String>,
+
+    /// Output format for the encoded results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+    format: OutputFormat,
 
     /// Number of components for X axis (1-9)
     #[arg(short = 'x', long, value_name = "NUM")]
@@ -18,109 +52,354 @@ struct Cli {
     /// Number of components for Y axis (1-9)
     #[arg(short = 'y', long, value_name = "NUM")]
     components_y: Option<u32>,
+
+    /// Only fetch remote images whose host matches one of these domains
+    /// (repeatable; parent-domain suffixes match, e.g. `example.com`)
+    #[arg(long, value_name = "DOMAIN")]
+    allow_domain: Vec<String>,
+
+    /// Never fetch remote images whose host matches one of these domains
+    /// (repeatable; parent-domain suffixes match, e.g. `example.com`)
+    #[arg(long, value_name = "DOMAIN")]
+    block_domain: Vec<String>,
+
+    /// Disable all network access; only local/`file:`/`data:` inputs are allowed
+    #[arg(long, alias = "allow-local")]
+    no_network: bool,
+
+    /// User-Agent header to send with remote requests
+    #[arg(long, value_name = "STRING")]
+    user_agent: Option<String>,
+
+    /// Request timeout in seconds
+    #[arg(long, value_name = "SECS")]
+    timeout: Option<u64>,
+
+    /// Maximum number of redirects to follow
+    #[arg(long, value_name = "NUM")]
+    max_redirects: Option<usize>,
+
+    /// Extra request header in `Name: Value` form (repeatable)
+    #[arg(long, value_name = "HEADER")]
+    header: Vec<String>,
+
+    /// Verify fetched bytes against a Subresource-Integrity digest,
+    /// e.g. `sha256-<base64>` (supports sha256, sha384, sha512)
+    #[arg(long, value_name = "DIGEST")]
+    integrity: Option<String>,
 }
 
-fn looks_like_url(s: &str) -> bool {
-    // First, check for common protocols
-    if s.starts_with("http://") || s.starts_with("https://") {
-        return true;
-    }
+/// Output format for `encode`, mirroring common CLI conventions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, ValueEnum)]
+enum OutputFormat {
+    /// One blurhash per line (the historical behaviour)
+    #[default]
+    Plain,
+    /// A single JSON array of result objects
+    Json,
+    /// One JSON result object per line, for streaming
+    Ndjson,
+}
 
-    // Check for common URL patterns
-    if s.starts_with("www.") {
-        return true;
-    }
+/// A single entry in an `encode` run's structured output.
+#[derive(Serialize)]
+struct EncodeResult {
+    source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    components_x: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    components_y: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
 
-    // Check for invalid URL patterns
-    if s.starts_with(".") {
-        return false;
+/// A Subresource-Integrity–style digest used to pin fetched image bytes.
+struct Integrity {
+    algo: IntegrityAlgo,
+    expected: String,
+}
+
+#[derive(Clone, Copy)]
+enum IntegrityAlgo {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Integrity {
+    /// Parse an `<algo>-<base64>` specification, e.g. `sha256-47DEQ...`.
+    fn parse(spec: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (algo, expected) = spec
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid integrity (expected `<algo>-<base64>`): {}", spec))?;
+        let algo = match algo {
+            "sha256" => IntegrityAlgo::Sha256,
+            "sha384" => IntegrityAlgo::Sha384,
+            "sha512" => IntegrityAlgo::Sha512,
+            other => return Err(format!("Unsupported integrity algorithm: {}", other).into()),
+        };
+        Ok(Integrity {
+            algo,
+            expected: expected.to_string(),
+        })
     }
 
-    // Try to parse as URL with added https:// if needed
-    let url_str = if !s.contains("://") {
-        format!("https://{}", s)
-    } else {
-        s.to_string()
-    };
-
-    if let Ok(url) = Url::parse(&url_str) {
-        // Check if it has a valid domain structure
-        if url.has_host() && url.domain().is_some() {
-            // Additional validation: should have at least one dot and valid TLD
-            if let Some(domain) = url.domain() {
-                return domain.contains('.') && 
-                       !domain.ends_with('.') && 
-                       !domain.contains('\\');  // Backslashes typically indicate local paths
-            }
+    /// Error out unless `bytes` hash to the expected base64 digest.
+    fn verify(&self, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let digest = match self.algo {
+            IntegrityAlgo::Sha256 => Sha256::digest(bytes).to_vec(),
+            IntegrityAlgo::Sha384 => Sha384::digest(bytes).to_vec(),
+            IntegrityAlgo::Sha512 => Sha512::digest(bytes).to_vec(),
+        };
+        let actual = base64::engine::general_purpose::STANDARD.encode(digest);
+        if actual != self.expected {
+            return Err(format!(
+                "Integrity check failed: expected {}, got {}",
+                self.expected, actual
+            )
+            .into());
         }
+        Ok(())
     }
-    false
 }
 
-fn looks_like_local_path(s: &str) -> bool {
-    // Check for absolute paths
-    if Path::new(s).is_absolute() {
-        return true;
+#[derive(Args)]
+struct DecodeArgs {
+    /// Blurhash string to decode
+    hash: String,
+
+    /// Width of the reconstructed image
+    #[arg(long, value_name = "PX")]
+    width: u32,
+
+    /// Height of the reconstructed image
+    #[arg(long, value_name = "PX")]
+    height: u32,
+
+    /// Punch (contrast) factor applied while decoding
+    #[arg(long, value_name = "NUM", default_value_t = 1.0)]
+    punch: f32,
+
+    /// Output PNG path; when omitted a `data:image/png;base64,...` URL is printed
+    #[arg(short = 'o', long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+/// Policy controlling which remote hosts `load_image` is permitted to fetch,
+/// together with the pre-built HTTP client used to fetch them.
+struct FetchConfig {
+    allow_domain: Vec<String>,
+    block_domain: Vec<String>,
+    no_network: bool,
+    client: Client,
+    integrity: Option<Integrity>,
+    /// In-memory cache of fetched bytes keyed by URL, to avoid refetching the
+    /// same remote image within a single invocation.
+    cache: RefCell<HashMap<String, Vec<u8>>>,
+}
+
+impl FetchConfig {
+    fn from_args(args: &EncodeArgs) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(FetchConfig {
+            allow_domain: args.allow_domain.clone(),
+            block_domain: args.block_domain.clone(),
+            no_network: args.no_network,
+            client: build_client(args)?,
+            integrity: args.integrity.as_deref().map(Integrity::parse).transpose()?,
+            cache: RefCell::new(HashMap::new()),
+        })
     }
 
-    // Check for common path patterns
-    if s.contains('\\') || s.contains('/') {
-        // Check if it starts with drive letter (Windows)
-        if s.len() >= 2 && s.chars().nth(1) == Some(':') {
-            return true;
+    /// Reject the fetch unless `host` passes the configured allow/block policy.
+    fn check_host(&self, host: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.no_network {
+            return Err("Network access is disabled (--no-network)".into());
+        }
+        if self.block_domain.iter().any(|d| domain_matches(host, d)) {
+            return Err(format!("Host {} is blocked by --block-domain", host).into());
         }
-        
-        // Check for relative paths with directory separators
-        if !s.contains("://") {
-            return true;
+        if !self.allow_domain.is_empty()
+            && !self.allow_domain.iter().any(|d| domain_matches(host, d))
+        {
+            return Err(format!("Host {} is not in the --allow-domain list", host).into());
         }
+        Ok(())
     }
+}
 
-    // Check for simple filenames with extensions
-    if s.contains('.') && !s.contains("://") && !s.starts_with("www.") {
-        let last_segment = Path::new(s).file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or("");
-        
-        // If it looks like a filename with extension
-        if last_segment.contains('.') && !last_segment.starts_with('.') {
-            return true;
-        }
+/// Match a host against an allow/block entry, honouring parent-domain suffixes
+/// so that `cdn.example.com` matches an entry of `example.com`.
+fn domain_matches(host: &str, entry: &str) -> bool {
+    host == entry || host.ends_with(&format!(".{}", entry))
+}
+
+/// Split a `Name: Value` header specification into its name and value.
+fn parse_header(spec: &str) -> Result<(HeaderName, HeaderValue), Box<dyn std::error::Error>> {
+    let (name, value) = spec
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid header (expected `Name: Value`): {}", spec))?;
+    let name = name.trim().parse::<HeaderName>()?;
+    let value = value.trim().parse::<HeaderValue>()?;
+    Ok((name, value))
+}
+
+/// Build a [`Client`] from the CLI's HTTP options (user agent, timeout,
+/// redirect cap and custom headers).
+fn build_client(args: &EncodeArgs) -> Result<Client, Box<dyn std::error::Error>> {
+    let mut headers = HeaderMap::new();
+    for spec in &args.header {
+        let (name, value) = parse_header(spec)?;
+        headers.insert(name, value);
     }
 
-    false
+    let mut builder = Client::builder().default_headers(headers);
+    if let Some(ua) = &args.user_agent {
+        builder = builder.user_agent(ua.clone());
+    }
+    if let Some(secs) = args.timeout {
+        builder = builder.timeout(Duration::from_secs(secs));
+    }
+    if let Some(max) = args.max_redirects {
+        builder = builder.redirect(Policy::limited(max));
+    }
+
+    Ok(builder.build()?)
 }
 
-fn load_image(source: &str) -> Result<image::DynamicImage, Box<dyn std::error::Error>> {
-    // If it looks like a URL and doesn't look like a local path
-    if looks_like_url(source) && !looks_like_local_path(source) {
-        // Handle cases where http(s):// is missing
-        let url = if !source.contains("://") {
-            format!("https://{}", source)
-        } else {
-            source.to_string()
-        };
+/// Convert a parsed `file:` URL into a filesystem path.
+///
+/// Percent-escapes in the path are decoded, a `localhost` authority is treated
+/// the same as an empty one, and a leading `/` in front of a Windows drive
+/// letter (e.g. `/C:/...`) is stripped so the result is a usable path.
+fn file_url_to_path(url: &Url) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    match url.host_str() {
+        None | Some("") | Some("localhost") => {}
+        Some(host) => return Err(format!("Unsupported file URL host: {}", host).into()),
+    }
 
-        let response = get(url)?;
-        if !response.status().is_success() {
-            return Err(format!("Failed to fetch image. Status: {}", response.status()).into());
-        }
-        let bytes = response.bytes()?;
-        Ok(image::load_from_memory(&bytes)?)
+    let decoded = percent_decode_str(url.path()).decode_utf8()?;
+    let path = decoded.as_ref();
+
+    // `/C:/foo` -> `C:/foo` on Windows-style drive paths.
+    let trimmed = path
+        .strip_prefix('/')
+        .filter(|rest| {
+            let mut chars = rest.chars();
+            matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+                && matches!(chars.next(), Some(':'))
+        })
+        .unwrap_or(path);
+
+    Ok(PathBuf::from(trimmed))
+}
+
+/// Decode a `data:` URL of the form `data:[<mediatype>][;base64],<data>` into
+/// its raw bytes.
+fn decode_data_url(source: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let rest = source
+        .strip_prefix("data:")
+        .ok_or("Not a data URL")?;
+    let (meta, payload) = rest
+        .split_once(',')
+        .ok_or("Malformed data URL: missing comma")?;
+
+    if meta.ends_with(";base64") {
+        Ok(base64::engine::general_purpose::STANDARD.decode(payload)?)
     } else {
-        // Treat as local path
-        Ok(image::open(Path::new(source))?)
+        Ok(percent_decode_str(payload).collect())
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+fn load_image(
+    source: &str,
+    config: &FetchConfig,
+) -> Result<image::DynamicImage, Box<dyn std::error::Error>> {
+    match Url::parse(source) {
+        Ok(url) => match url.scheme() {
+            "http" | "https" => {
+                let host = url.host_str().ok_or("Remote URL has no host")?;
+                config.check_host(host)?;
+
+                // Serve a previously fetched copy of the same URL if we have one.
+                let requested = url.as_str().to_string();
+                if let Some(bytes) = config.cache.borrow().get(&requested) {
+                    return Ok(image::load_from_memory(bytes)?);
+                }
+
+                let response = config.client.get(url).send()?;
+                if !response.status().is_success() {
+                    return Err(
+                        format!("Failed to fetch image. Status: {}", response.status()).into(),
+                    );
+                }
+                if let Some(content_type) = response.headers().get(CONTENT_TYPE) {
+                    let content_type = content_type.to_str().unwrap_or("");
+                    if !content_type.starts_with("image/") {
+                        return Err(format!(
+                            "Unexpected Content-Type for image: {}",
+                            content_type
+                        )
+                        .into());
+                    }
+                }
+                let final_url = response.url().to_string();
+                let bytes = response.bytes()?.to_vec();
+
+                if let Some(integrity) = &config.integrity {
+                    integrity.verify(&bytes)?;
+                }
+
+                let img = image::load_from_memory(&bytes)?;
+                // Cache under both the requested and final (post-redirect) URLs
+                // so either spelling is a hit next time.
+                let mut cache = config.cache.borrow_mut();
+                cache.insert(final_url, bytes.clone());
+                cache.insert(requested, bytes);
+                Ok(img)
+            }
+            "file" => Ok(image::open(file_url_to_path(&url)?)?),
+            "data" => Ok(image::load_from_memory(&decode_data_url(source)?)?),
+            // Unrecognized scheme (e.g. a Windows drive letter parsed as one):
+            // fall back to treating the input as a local path.
+            _ => Ok(image::open(Path::new(source))?),
+        },
+        // Not a URL at all: treat as a local path.
+        Err(_) => Ok(image::open(Path::new(source))?),
+    }
+}
+
+/// Encode a single image into a blurhash, returning its dimensions and hash.
+fn encode_one(
+    source: &str,
+    components_x: u32,
+    components_y: u32,
+    config: &FetchConfig,
+) -> Result<(u32, u32, String), Box<dyn std::error::Error>> {
+    let img = load_image(source, config).map_err(|e| format!("Failed to load image: {}", e))?;
+    let (width, height) = img.dimensions();
+    let pixels: Vec<u8> = img.to_rgba8().into_raw();
+    let hash = encode(components_x, components_y, width, height, &pixels)?;
+    Ok((width, height, hash))
+}
 
-    let components_x = cli.components_x.unwrap_or(4);
-    let components_y = cli.components_y.unwrap_or(3);
+/// Encode one or more images into blurhashes, emitting results in the requested
+/// format. Failures loading or encoding an individual input are recorded
+/// per-item rather than aborting the run, and the process then exits non-zero
+/// only when every input failed. Invalid arguments (component counts, glob
+/// patterns, header or integrity specs) return an error before any input is
+/// processed.
+fn run_encode(args: EncodeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let components_x = args.components_x.unwrap_or(4);
+    let components_y = args.components_y.unwrap_or(3);
 
     // Validate that if one component is specified, both must be
-    if cli.components_x.is_some() != cli.components_y.is_some() {
+    if args.components_x.is_some() != args.components_y.is_some() {
         return Err("If specifying components, both -x and -y must be provided".into());
     }
 
@@ -129,149 +408,321 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Err("The values of each component needs to be 1-9".into());
     }
 
-    let img = load_image(&cli.image).map_err(|e| format!("Failed to load image: {}", e))?;
-    let (width, height) = img.dimensions();
-    let pixels: Vec<u8> = img.to_rgba8().into_raw();
+    // Gather inputs from the positional list and any glob patterns.
+    let mut sources = args.inputs.clone();
+    for pattern in &args.glob {
+        for entry in glob::glob(pattern)? {
+            sources.push(entry?.display().to_string());
+        }
+    }
+    if sources.is_empty() {
+        return Err("No input images provided".into());
+    }
+
+    let config = FetchConfig::from_args(&args)?;
+
+    let results: Vec<EncodeResult> = sources
+        .iter()
+        .map(|source| match encode_one(source, components_x, components_y, &config) {
+            Ok((width, height, hash)) => EncodeResult {
+                source: source.clone(),
+                width: Some(width),
+                height: Some(height),
+                components_x: Some(components_x),
+                components_y: Some(components_y),
+                hash: Some(hash),
+                error: None,
+            },
+            Err(e) => EncodeResult {
+                source: source.clone(),
+                width: None,
+                height: None,
+                components_x: None,
+                components_y: None,
+                hash: None,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect();
+
+    match args.format {
+        OutputFormat::Plain => {
+            for result in &results {
+                match (&result.hash, &result.error) {
+                    (Some(hash), _) => println!("{}", hash),
+                    (None, Some(error)) => eprintln!("{}: {}", result.source, error),
+                    (None, None) => {}
+                }
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        OutputFormat::Ndjson => {
+            for result in &results {
+                println!("{}", serde_json::to_string(result)?);
+            }
+        }
+    }
+
+    // Fail the run only when nothing succeeded.
+    if results.iter().all(|r| r.error.is_some()) {
+        std::process::exit(1);
+    }
 
-    let blurhash = encode(components_x, components_y, width, height, &pixels);
-    println!("{}", blurhash.expect("Error during Blurhash encoding"));
+    Ok(())
+}
+
+/// Decode a blurhash into an image, writing a PNG to `--output` or printing a
+/// `data:` URL to stdout when no output path is given.
+fn run_decode(args: DecodeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let pixels = decode(&args.hash, args.width, args.height, args.punch)?;
+    let buffer = RgbaImage::from_raw(args.width, args.height, pixels)
+        .ok_or("Decoded pixel buffer does not match the requested dimensions")?;
+    let img = DynamicImage::ImageRgba8(buffer);
+
+    match &args.output {
+        Some(path) => img.save_with_format(path, ImageFormat::Png)?,
+        None => {
+            let mut bytes: Vec<u8> = Vec::new();
+            img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+            let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
+            println!("data:image/png;base64,{}", encoded);
+        }
+    }
 
     Ok(())
 }
 
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Encode(args) => run_encode(args),
+        Commands::Decode(args) => run_decode(args),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_looks_like_url() {
-        // Test valid URLs
-        assert!(looks_like_url("https://example.com"));
-        assert!(looks_like_url("http://example.com"));
-        assert!(looks_like_url("www.example.com"));
-        assert!(looks_like_url("example.com/path"));
-        assert!(looks_like_url("subdomain.example.com"));
-        assert!(looks_like_url("example.com/image.jpg"));
-        assert!(looks_like_url("cdn.example.com/assets/img.png"));
-        assert!(looks_like_url("http://localhost")); // Local URLs
-        assert!(looks_like_url("https://localhost:8080")); // Local URLs with port
-        
-        // Test invalid URLs
-        assert!(!looks_like_url("C:\\path\\to\\file.jpg"));
-        assert!(!looks_like_url("/usr/local/file.jpg"));
-        assert!(!looks_like_url("just-text"));
-        assert!(!looks_like_url(""));
-        assert!(!looks_like_url("example"));
-        assert!(!looks_like_url("example."));
-        assert!(!looks_like_url(".example"));
+    fn test_file_url_to_path() {
+        // Plain Unix path with an empty authority.
+        let url = Url::parse("file:///usr/local/images/test.jpg").unwrap();
+        assert_eq!(
+            file_url_to_path(&url).unwrap(),
+            PathBuf::from("/usr/local/images/test.jpg")
+        );
+
+        // `localhost` authority behaves like an empty one.
+        let url = Url::parse("file://localhost/tmp/pic.png").unwrap();
+        assert_eq!(file_url_to_path(&url).unwrap(), PathBuf::from("/tmp/pic.png"));
+
+        // Percent-escapes are decoded.
+        let url = Url::parse("file:///tmp/my%20image.jpg").unwrap();
+        assert_eq!(
+            file_url_to_path(&url).unwrap(),
+            PathBuf::from("/tmp/my image.jpg")
+        );
+
+        // A leading slash before a Windows drive letter is stripped.
+        let url = Url::parse("file:///C:/Users/test/image.jpg").unwrap();
+        assert_eq!(
+            file_url_to_path(&url).unwrap(),
+            PathBuf::from("C:/Users/test/image.jpg")
+        );
+
+        // A foreign authority is rejected.
+        let url = Url::parse("file://server/share/image.jpg").unwrap();
+        assert!(file_url_to_path(&url).is_err());
+    }
+
+    #[test]
+    fn test_decode_data_url() {
+        // base64 payload ("Hi").
+        assert_eq!(decode_data_url("data:image/png;base64,SGk=").unwrap(), b"Hi");
+
+        // percent-decoded payload.
+        assert_eq!(
+            decode_data_url("data:text/plain,Hello%20World").unwrap(),
+            b"Hello World"
+        );
+
+        // No media type, plain payload.
+        assert_eq!(decode_data_url("data:,abc").unwrap(), b"abc");
+
+        // Missing comma is an error.
+        assert!(decode_data_url("data:image/png;base64").is_err());
+        assert!(decode_data_url("https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_domain_matches() {
+        // Exact host.
+        assert!(domain_matches("example.com", "example.com"));
+        // Parent-domain suffix.
+        assert!(domain_matches("cdn.example.com", "example.com"));
+        assert!(domain_matches("a.b.example.com", "example.com"));
+        // Non-matches must not be fooled by substrings.
+        assert!(!domain_matches("notexample.com", "example.com"));
+        assert!(!domain_matches("example.com.evil.com", "example.com"));
+        assert!(!domain_matches("example.org", "example.com"));
+    }
+
+    #[test]
+    fn test_check_host() {
+        // A blocklisted host is rejected; other hosts pass.
+        let config = FetchConfig {
+            allow_domain: vec![],
+            block_domain: vec!["internal.example".to_string()],
+            no_network: false,
+            client: Client::new(),
+            integrity: None,
+            cache: RefCell::new(HashMap::new()),
+        };
+        assert!(config.check_host("internal.example").is_err());
+        assert!(config.check_host("public.example").is_ok());
+
+        // A non-empty allowlist rejects anything outside it.
+        let config = FetchConfig {
+            allow_domain: vec!["example.com".to_string()],
+            block_domain: vec![],
+            no_network: false,
+            client: Client::new(),
+            integrity: None,
+            cache: RefCell::new(HashMap::new()),
+        };
+        assert!(config.check_host("cdn.example.com").is_ok());
+        assert!(config.check_host("evil.com").is_err());
+
+        // --no-network blocks unconditionally.
+        let config = FetchConfig {
+            allow_domain: vec!["example.com".to_string()],
+            block_domain: vec![],
+            no_network: true,
+            client: Client::new(),
+            integrity: None,
+            cache: RefCell::new(HashMap::new()),
+        };
+        assert!(config.check_host("example.com").is_err());
+    }
+
+    #[test]
+    fn test_integrity() {
+        // Known sha256 of the bytes "abc".
+        let expected = base64::engine::general_purpose::STANDARD
+            .encode(Sha256::digest(b"abc"));
+        let integrity = Integrity::parse(&format!("sha256-{}", expected)).unwrap();
+        assert!(integrity.verify(b"abc").is_ok());
+        assert!(integrity.verify(b"abcd").is_err());
+
+        // Algorithm variants parse.
+        assert!(Integrity::parse("sha384-AAAA").is_ok());
+        assert!(Integrity::parse("sha512-AAAA").is_ok());
+
+        // Unknown algorithm and malformed specs are rejected.
+        assert!(Integrity::parse("md5-AAAA").is_err());
+        assert!(Integrity::parse("sha256").is_err());
     }
 
     #[test]
-    fn test_looks_like_local_path() {
-        // Test Windows-style paths
-        assert!(looks_like_local_path("C:\\Users\\test\\image.jpg"));
-        assert!(looks_like_local_path("D:\\photos\\vacation\\pic.png"));
-        assert!(looks_like_local_path(".\\relative\\path.jpg"));
-        assert!(looks_like_local_path("..\\parent\\path.jpg"));
-        assert!(looks_like_local_path("folder\\subfolder\\image.jpg"));
-        
-        // Test Unix-style paths
-        assert!(looks_like_local_path("/usr/local/images/test.jpg"));
-        assert!(looks_like_local_path("./relative/path.jpg"));
-        assert!(looks_like_local_path("../parent/path.jpg"));
-        assert!(looks_like_local_path("folder/subfolder/image.jpg"));
-        assert!(looks_like_local_path("/root/path.jpg"));
-        
-        // Test simple filenames
-        assert!(looks_like_local_path("image.jpg"));
-        assert!(looks_like_local_path("document.pdf"));
-        assert!(looks_like_local_path("test-file.png"));
-        assert!(looks_like_local_path("my.complex.file.name.jpg"));
-        
-        // Test invalid paths
-        assert!(!looks_like_local_path("https://example.com/image.jpg"));
-        assert!(!looks_like_local_path("http://example.com/image.jpg"));
-        assert!(!looks_like_local_path("")); // Empty string
-        assert!(!looks_like_local_path("noextension"));
-        assert!(!looks_like_local_path(".hidden")); // Hidden file without extension
-        assert!(!looks_like_local_path(".")); // Current directory
-        assert!(!looks_like_local_path("..")); // Parent directory
+    fn test_parse_header() {
+        let (name, value) = parse_header("Authorization: Bearer token").unwrap();
+        assert_eq!(name.as_str(), "authorization");
+        assert_eq!(value.to_str().unwrap(), "Bearer token");
+
+        // Surrounding whitespace is trimmed.
+        let (name, value) = parse_header("X-Api-Key:  abc123  ").unwrap();
+        assert_eq!(name.as_str(), "x-api-key");
+        assert_eq!(value.to_str().unwrap(), "abc123");
+
+        // Missing colon is an error.
+        assert!(parse_header("NoColonHere").is_err());
     }
 
     #[test]
     fn test_cli_parameters() {
         // Test valid component ranges
-        let cli = Cli {
-            image: "test.jpg".to_string(),
+        let cli = EncodeArgs {
+            inputs: vec!["test.jpg".to_string()],
             components_x: Some(4),
             components_y: Some(3),
+            ..Default::default()
         };
         assert!(validate_cli_parameters(&cli).is_ok());
 
         // Test minimum valid values
-        let cli = Cli {
-            image: "test.jpg".to_string(),
+        let cli = EncodeArgs {
+            inputs: vec!["test.jpg".to_string()],
             components_x: Some(1),
             components_y: Some(1),
+            ..Default::default()
         };
         assert!(validate_cli_parameters(&cli).is_ok());
 
         // Test maximum valid values
-        let cli = Cli {
-            image: "test.jpg".to_string(),
+        let cli = EncodeArgs {
+            inputs: vec!["test.jpg".to_string()],
             components_x: Some(9),
             components_y: Some(9),
+            ..Default::default()
         };
         assert!(validate_cli_parameters(&cli).is_ok());
 
         // Test invalid component ranges
-        let cli = Cli {
-            image: "test.jpg".to_string(),
+        let cli = EncodeArgs {
+            inputs: vec!["test.jpg".to_string()],
             components_x: Some(10),
             components_y: Some(3),
+            ..Default::default()
         };
         assert!(validate_cli_parameters(&cli).is_err());
 
-        let cli = Cli {
-            image: "test.jpg".to_string(),
+        let cli = EncodeArgs {
+            inputs: vec!["test.jpg".to_string()],
             components_x: Some(4),
             components_y: Some(10),
+            ..Default::default()
         };
         assert!(validate_cli_parameters(&cli).is_err());
 
-        let cli = Cli {
-            image: "test.jpg".to_string(),
+        let cli = EncodeArgs {
+            inputs: vec!["test.jpg".to_string()],
             components_x: Some(0),
             components_y: Some(3),
+            ..Default::default()
         };
         assert!(validate_cli_parameters(&cli).is_err());
 
         // Test missing components
-        let cli = Cli {
-            image: "test.jpg".to_string(),
+        let cli = EncodeArgs {
+            inputs: vec!["test.jpg".to_string()],
             components_x: Some(4),
             components_y: None,
+            ..Default::default()
         };
         assert!(validate_cli_parameters(&cli).is_err());
 
-        let cli = Cli {
-            image: "test.jpg".to_string(),
+        let cli = EncodeArgs {
+            inputs: vec!["test.jpg".to_string()],
             components_x: None,
             components_y: Some(3),
+            ..Default::default()
         };
         assert!(validate_cli_parameters(&cli).is_err());
 
         // Test default values (both None is valid)
-        let cli = Cli {
-            image: "test.jpg".to_string(),
+        let cli = EncodeArgs {
+            inputs: vec!["test.jpg".to_string()],
             components_x: None,
             components_y: None,
+            ..Default::default()
         };
         assert!(validate_cli_parameters(&cli).is_ok());
     }
 
     // Helper function for validating CLI parameters
-    fn validate_cli_parameters(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    fn validate_cli_parameters(cli: &EncodeArgs) -> Result<(), Box<dyn std::error::Error>> {
         let components_x = cli.components_x.unwrap_or(4);
         let components_y = cli.components_y.unwrap_or(3);
 